@@ -0,0 +1,310 @@
+//! Reads and writes PaX markings stored directly in the ELF `PT_PAX_FLAGS`
+//! program header, as an alternative to the `user.pax.flags` xattr.
+//!
+//! Unlike the xattr, this survives filesystems without extended attribute
+//! support and binaries distributed with their xattrs stripped, at the cost
+//! of needing the segment to already exist (PaX-aware linkers emit it; it
+//! cannot be added here without relinking).
+
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use object::{
+    read::elf::{FileHeader, ProgramHeader},
+    Endianness, FileKind,
+};
+
+use crate::flags::{Delta, Feature, PaxFlags};
+
+/// `PT_PAX_FLAGS`: a PaX-specific program header type, not part of the
+/// generic ELF spec. The segment carries no memory of its own; PaX reuses
+/// its `p_flags` field to store the marks instead.
+const PT_PAX_FLAGS: u32 = 0x6504_1580;
+
+impl Feature {
+    /// The force-on bit for this feature in the `PT_PAX_FLAGS` `p_flags`
+    /// encoding, per the PaX `MARKINGS` layout used by `paxctl`/the PaX
+    /// kernel patch. Bits 0-3 are reserved and bits 10-11 (RANDEXEC) are
+    /// unused by this tool.
+    fn elf_on_bit(self) -> u32 {
+        match self {
+            Feature::PageExec => 1 << 4,
+            Feature::Segmexec => 1 << 6,
+            Feature::Mprotect => 1 << 8,
+            Feature::Emutramp => 1 << 12,
+            Feature::Randmmap => 1 << 14,
+        }
+    }
+
+    /// The force-off bit for this feature in the `PT_PAX_FLAGS` `p_flags`
+    /// encoding, immediately following the force-on bit.
+    fn elf_off_bit(self) -> u32 {
+        self.elf_on_bit() << 1
+    }
+}
+
+impl PaxFlags {
+    /// Decodes the marks from a `PT_PAX_FLAGS` segment's raw `p_flags`.
+    fn from_elf_p_flags(p_flags: u32) -> PaxFlags {
+        let mut flags = PaxFlags::empty();
+        for feature in Feature::ALL {
+            let on = p_flags & feature.elf_on_bit() != 0;
+            let off = p_flags & feature.elf_off_bit() != 0;
+            flags.set_delta(feature, Delta::new(on, off));
+        }
+        flags
+    }
+
+    /// Encodes the marks into a `PT_PAX_FLAGS` segment's raw `p_flags`.
+    fn to_elf_p_flags(self) -> u32 {
+        Feature::ALL.into_iter().fold(0, |bits, feature| {
+            bits | match self.get(feature) {
+                Delta::Enable => feature.elf_on_bit(),
+                Delta::Disable => feature.elf_off_bit(),
+                Delta::Keep => 0,
+            }
+        })
+    }
+}
+
+/// Reads the PaX marks stored in the binary's `PT_PAX_FLAGS` program
+/// header, if it has one.
+pub fn get_value(binary: impl AsRef<Path>) -> io::Result<Option<PaxFlags>> {
+    Ok(raw_p_flags(binary)?.map(PaxFlags::from_elf_p_flags))
+}
+
+/// Reads the raw `p_flags` bits of the `PT_PAX_FLAGS` segment, if present,
+/// without decoding them into [`PaxFlags`].
+pub fn raw_p_flags(binary: impl AsRef<Path>) -> io::Result<Option<u32>> {
+    let data = fs::read(binary)?;
+    Ok(find_pax_segment(&data)?.map(|(_, p_flags)| p_flags))
+}
+
+/// Rewrites the `PT_PAX_FLAGS` segment's `p_flags` in place.
+///
+/// Errors out if the binary has no `PT_PAX_FLAGS` segment, since one can't
+/// be added here without relinking.
+pub fn set_value(binary: impl AsRef<Path>, value: PaxFlags) -> io::Result<()> {
+    let binary = binary.as_ref();
+    let data = fs::read(binary)?;
+    let Some((p_flags_offset, _)) = find_pax_segment(&data)? else {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "binary has no PT_PAX_FLAGS segment; it cannot be added without relinking",
+        ));
+    };
+
+    let endian = file_endian(&data)?;
+    let bytes = match endian {
+        Endianness::Little => value.to_elf_p_flags().to_le_bytes(),
+        Endianness::Big => value.to_elf_p_flags().to_be_bytes(),
+    };
+
+    let mut file = OpenOptions::new().write(true).open(binary)?;
+    file.seek(SeekFrom::Start(p_flags_offset))?;
+    file.write_all(&bytes)
+}
+
+fn file_endian(data: &[u8]) -> io::Result<Endianness> {
+    match FileKind::parse(data) {
+        Ok(FileKind::Elf32) => Ok(object::elf::FileHeader32::<Endianness>::parse(data)
+            .map_err(invalid_elf)?
+            .endian()
+            .map_err(invalid_elf)?),
+        Ok(FileKind::Elf64) => Ok(object::elf::FileHeader64::<Endianness>::parse(data)
+            .map_err(invalid_elf)?
+            .endian()
+            .map_err(invalid_elf)?),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "not an ELF binary")),
+    }
+}
+
+/// Finds the `PT_PAX_FLAGS` segment, returning the file offset of its
+/// `p_flags` field alongside its current value.
+fn find_pax_segment(data: &[u8]) -> io::Result<Option<(u64, u32)>> {
+    match FileKind::parse(data) {
+        Ok(FileKind::Elf32) => find_pax_segment_32(data),
+        Ok(FileKind::Elf64) => find_pax_segment_64(data),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "not an ELF binary")),
+    }
+}
+
+fn find_pax_segment_64(data: &[u8]) -> io::Result<Option<(u64, u32)>> {
+    let header = object::elf::FileHeader64::<Endianness>::parse(data).map_err(invalid_elf)?;
+    let endian = header.endian().map_err(invalid_elf)?;
+    let segments = header.program_headers(endian, data).map_err(invalid_elf)?;
+    let phoff = header.e_phoff(endian);
+    let phentsize = u64::from(header.e_phentsize(endian));
+
+    for (index, segment) in segments.iter().enumerate() {
+        if segment.p_type(endian) == PT_PAX_FLAGS {
+            // p_flags is the second field of Elf64_Phdr, right after p_type.
+            let offset = phoff + index as u64 * phentsize + 4;
+            return Ok(Some((offset, segment.p_flags(endian))));
+        }
+    }
+    Ok(None)
+}
+
+fn find_pax_segment_32(data: &[u8]) -> io::Result<Option<(u64, u32)>> {
+    let header = object::elf::FileHeader32::<Endianness>::parse(data).map_err(invalid_elf)?;
+    let endian = header.endian().map_err(invalid_elf)?;
+    let segments = header.program_headers(endian, data).map_err(invalid_elf)?;
+    let phoff = u64::from(header.e_phoff(endian));
+    let phentsize = u64::from(header.e_phentsize(endian));
+
+    for (index, segment) in segments.iter().enumerate() {
+        if segment.p_type(endian) == PT_PAX_FLAGS {
+            // p_flags is the seventh field of Elf32_Phdr.
+            let offset = phoff + index as u64 * phentsize + 24;
+            return Ok(Some((offset, segment.p_flags(endian))));
+        }
+    }
+    Ok(None)
+}
+
+fn invalid_elf(err: object::read::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn p_flags_roundtrip() {
+        let mut flags = PaxFlags::empty();
+        flags.set_delta(Feature::PageExec, Delta::Enable);
+        flags.set_delta(Feature::Mprotect, Delta::Disable);
+
+        let decoded = PaxFlags::from_elf_p_flags(flags.to_elf_p_flags());
+        assert_eq!(decoded.get(Feature::PageExec), Delta::Enable);
+        assert_eq!(decoded.get(Feature::Mprotect), Delta::Disable);
+        assert_eq!(decoded.get(Feature::Emutramp), Delta::Keep);
+    }
+
+    /// Builds a minimal little-endian ELF64 file: just enough header and
+    /// program header table for `find_pax_segment_64` to work with, with a
+    /// `PT_LOAD` segment ahead of the `PT_PAX_FLAGS` one so the offset math
+    /// has to account for `phentsize`/index, not just `phoff`.
+    fn build_elf64(pax_p_flags: u32) -> Vec<u8> {
+        const EHDR_SIZE: u16 = 64;
+        const PHDR_SIZE: u16 = 56;
+        let phoff: u64 = EHDR_SIZE.into();
+
+        let mut data = vec![0u8; 0];
+        data.extend_from_slice(b"\x7fELF");
+        data.push(2); // ELFCLASS64
+        data.push(1); // ELFDATA2LSB
+        data.push(1); // EV_CURRENT
+        data.extend_from_slice(&[0u8; 9]); // remaining e_ident padding
+        data.extend_from_slice(&0u16.to_le_bytes()); // e_type
+        data.extend_from_slice(&0u16.to_le_bytes()); // e_machine
+        data.extend_from_slice(&1u32.to_le_bytes()); // e_version
+        data.extend_from_slice(&0u64.to_le_bytes()); // e_entry
+        data.extend_from_slice(&phoff.to_le_bytes()); // e_phoff
+        data.extend_from_slice(&0u64.to_le_bytes()); // e_shoff
+        data.extend_from_slice(&0u32.to_le_bytes()); // e_flags
+        data.extend_from_slice(&EHDR_SIZE.to_le_bytes()); // e_ehsize
+        data.extend_from_slice(&PHDR_SIZE.to_le_bytes()); // e_phentsize
+        data.extend_from_slice(&2u16.to_le_bytes()); // e_phnum
+        data.extend_from_slice(&0u16.to_le_bytes()); // e_shentsize
+        data.extend_from_slice(&0u16.to_le_bytes()); // e_shnum
+        data.extend_from_slice(&0u16.to_le_bytes()); // e_shstrndx
+        assert_eq!(data.len() as u64, phoff);
+
+        // Phdr 0: an unrelated PT_LOAD segment.
+        data.extend_from_slice(&1u32.to_le_bytes()); // p_type = PT_LOAD
+        data.extend_from_slice(&0u32.to_le_bytes()); // p_flags
+        data.extend_from_slice(&[0u8; 48]); // p_offset..p_align
+
+        // Phdr 1: the PT_PAX_FLAGS segment under test.
+        data.extend_from_slice(&PT_PAX_FLAGS.to_le_bytes()); // p_type
+        data.extend_from_slice(&pax_p_flags.to_le_bytes()); // p_flags
+        data.extend_from_slice(&[0u8; 48]); // p_offset..p_align
+
+        data
+    }
+
+    #[test]
+    fn find_pax_segment_64_locates_p_flags_offset() {
+        let data = build_elf64(0xdead_beef);
+        let (offset, p_flags) = find_pax_segment_64(&data).unwrap().unwrap();
+
+        // Phdr 1 starts right after Phdr 0; p_flags is its second field.
+        let expected_offset = 64 /* ehdr */ + 56 /* phdr 0 */ + 4;
+        assert_eq!(offset, expected_offset);
+        assert_eq!(p_flags, 0xdead_beef);
+
+        // The offset should genuinely point at the live p_flags bytes.
+        assert_eq!(&data[offset as usize..offset as usize + 4], &0xdead_beef_u32.to_le_bytes());
+    }
+
+    #[test]
+    fn find_pax_segment_64_absent_returns_none() {
+        let mut data = build_elf64(0);
+        // Turn the PT_PAX_FLAGS phdr into a second PT_LOAD.
+        let pax_type_offset = 64 + 56;
+        data[pax_type_offset..pax_type_offset + 4].copy_from_slice(&1u32.to_le_bytes());
+
+        assert_eq!(find_pax_segment_64(&data).unwrap(), None);
+    }
+
+    /// Builds a minimal little-endian ELF32 file, mirroring `build_elf64`.
+    fn build_elf32(pax_p_flags: u32) -> Vec<u8> {
+        const EHDR_SIZE: u16 = 52;
+        const PHDR_SIZE: u16 = 32;
+        let phoff: u32 = EHDR_SIZE.into();
+
+        let mut data = vec![0u8; 0];
+        data.extend_from_slice(b"\x7fELF");
+        data.push(1); // ELFCLASS32
+        data.push(1); // ELFDATA2LSB
+        data.push(1); // EV_CURRENT
+        data.extend_from_slice(&[0u8; 9]);
+        data.extend_from_slice(&0u16.to_le_bytes()); // e_type
+        data.extend_from_slice(&0u16.to_le_bytes()); // e_machine
+        data.extend_from_slice(&1u32.to_le_bytes()); // e_version
+        data.extend_from_slice(&0u32.to_le_bytes()); // e_entry
+        data.extend_from_slice(&phoff.to_le_bytes()); // e_phoff
+        data.extend_from_slice(&0u32.to_le_bytes()); // e_shoff
+        data.extend_from_slice(&0u32.to_le_bytes()); // e_flags
+        data.extend_from_slice(&EHDR_SIZE.to_le_bytes()); // e_ehsize
+        data.extend_from_slice(&PHDR_SIZE.to_le_bytes()); // e_phentsize
+        data.extend_from_slice(&2u16.to_le_bytes()); // e_phnum
+        data.extend_from_slice(&0u16.to_le_bytes()); // e_shentsize
+        data.extend_from_slice(&0u16.to_le_bytes()); // e_shnum
+        data.extend_from_slice(&0u16.to_le_bytes()); // e_shstrndx
+        assert_eq!(data.len() as u32, phoff);
+
+        // Phdr 0: an unrelated PT_LOAD segment (p_type, p_offset, p_vaddr,
+        // p_paddr, p_filesz, p_memsz, p_flags, p_align).
+        data.extend_from_slice(&1u32.to_le_bytes()); // p_type = PT_LOAD
+        data.extend_from_slice(&[0u8; 20]); // p_offset..p_memsz
+        data.extend_from_slice(&0u32.to_le_bytes()); // p_flags
+        data.extend_from_slice(&[0u8; 4]); // p_align
+
+        // Phdr 1: the PT_PAX_FLAGS segment under test. p_flags is the
+        // seventh field, not the second, for Elf32_Phdr.
+        data.extend_from_slice(&PT_PAX_FLAGS.to_le_bytes()); // p_type
+        data.extend_from_slice(&[0u8; 20]); // p_offset..p_memsz
+        data.extend_from_slice(&pax_p_flags.to_le_bytes()); // p_flags
+        data.extend_from_slice(&[0u8; 4]); // p_align
+
+        data
+    }
+
+    #[test]
+    fn find_pax_segment_32_locates_p_flags_offset() {
+        let data = build_elf32(0x1234_5678);
+        let (offset, p_flags) = find_pax_segment_32(&data).unwrap().unwrap();
+
+        let expected_offset = 52 /* ehdr */ + 32 /* phdr 0 */ + 24;
+        assert_eq!(offset, expected_offset);
+        assert_eq!(p_flags, 0x1234_5678);
+        assert_eq!(&data[offset as usize..offset as usize + 4], &0x1234_5678_u32.to_le_bytes());
+    }
+}
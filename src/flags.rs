@@ -0,0 +1,261 @@
+//! Typed representation of PaX markings.
+//!
+//! PaX exposes five independent features. Each one is tri-state: forced on,
+//! forced off, or left at the kernel default. [`PaxFlags`] stores that as a
+//! pair of bits per feature (the same on/off pairing the ELF `PT_PAX_FLAGS`
+//! encoding uses, though at different bit positions), and
+//! [`Feature`] + [`Delta`] give a typed way to read and write individual
+//! marks without juggling raw characters.
+
+use std::fmt;
+
+use bitflags::bitflags;
+
+bitflags! {
+    /// Raw PaX marking bits.
+    ///
+    /// Each feature occupies a pair of bits: a force-on bit and a force-off
+    /// bit. Neither bit set means "kernel default"; both bits set is treated
+    /// the same as force-on (on wins, as in [`Delta::new`]).
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+    pub struct PaxFlags: u16 {
+        const PAGEEXEC_ON  = 0b0000_0000_0001;
+        const PAGEEXEC_OFF = 0b0000_0000_0010;
+        const EMUTRAMP_ON  = 0b0000_0000_0100;
+        const EMUTRAMP_OFF = 0b0000_0000_1000;
+        const MPROTECT_ON  = 0b0000_0001_0000;
+        const MPROTECT_OFF = 0b0000_0010_0000;
+        const RANDMMAP_ON  = 0b0000_0100_0000;
+        const RANDMMAP_OFF = 0b0000_1000_0000;
+        const SEGMEXEC_ON  = 0b0001_0000_0000;
+        const SEGMEXEC_OFF = 0b0010_0000_0000;
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for PaxFlags {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for PaxFlags {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        PaxFlags::parse(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// The five PaX features, in their canonical `PEMRS` display order.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Feature {
+    PageExec,
+    Emutramp,
+    Mprotect,
+    Randmmap,
+    Segmexec,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 5] = [
+        Feature::PageExec,
+        Feature::Emutramp,
+        Feature::Mprotect,
+        Feature::Randmmap,
+        Feature::Segmexec,
+    ];
+
+    /// The canonical uppercase letter for this feature, as used in `PEMRS`.
+    pub fn letter(self) -> char {
+        match self {
+            Feature::PageExec => 'P',
+            Feature::Emutramp => 'E',
+            Feature::Mprotect => 'M',
+            Feature::Randmmap => 'R',
+            Feature::Segmexec => 'S',
+        }
+    }
+
+    fn from_letter(c: char) -> Option<Feature> {
+        Feature::ALL.into_iter().find(|f| f.letter() == c)
+    }
+
+    fn on_bit(self) -> PaxFlags {
+        match self {
+            Feature::PageExec => PaxFlags::PAGEEXEC_ON,
+            Feature::Emutramp => PaxFlags::EMUTRAMP_ON,
+            Feature::Mprotect => PaxFlags::MPROTECT_ON,
+            Feature::Randmmap => PaxFlags::RANDMMAP_ON,
+            Feature::Segmexec => PaxFlags::SEGMEXEC_ON,
+        }
+    }
+
+    fn off_bit(self) -> PaxFlags {
+        match self {
+            Feature::PageExec => PaxFlags::PAGEEXEC_OFF,
+            Feature::Emutramp => PaxFlags::EMUTRAMP_OFF,
+            Feature::Mprotect => PaxFlags::MPROTECT_OFF,
+            Feature::Randmmap => PaxFlags::RANDMMAP_OFF,
+            Feature::Segmexec => PaxFlags::SEGMEXEC_OFF,
+        }
+    }
+}
+
+/// The error produced when parsing a mark string fails.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParseFlagsError {
+    #[error("unknown PaX mark '{0}'")]
+    UnknownMark(char),
+    #[error("duplicate PaX mark for feature '{0}'")]
+    DuplicateMark(char),
+}
+
+impl PaxFlags {
+    /// Parses a PaX mark string such as `"PeMrS"` into typed flags.
+    ///
+    /// Unlike the legacy parser, unknown characters and marks repeated for
+    /// the same feature are rejected outright instead of being silently
+    /// dropped.
+    pub fn parse(value: &str) -> Result<PaxFlags, ParseFlagsError> {
+        let mut flags = PaxFlags::empty();
+        for c in value.chars() {
+            let feature =
+                Feature::from_letter(c.to_ascii_uppercase()).ok_or(ParseFlagsError::UnknownMark(c))?;
+            if flags.intersects(feature.on_bit() | feature.off_bit()) {
+                return Err(ParseFlagsError::DuplicateMark(feature.letter()));
+            }
+            let delta = if c.is_ascii_uppercase() { Delta::Enable } else { Delta::Disable };
+            flags.set_delta(feature, delta);
+        }
+        Ok(flags)
+    }
+
+    /// Reads the tri-state mark for a single feature.
+    pub fn get(self, feature: Feature) -> Delta {
+        let on = self.contains(feature.on_bit());
+        let off = self.contains(feature.off_bit());
+        match (on, off) {
+            (true, _) => Delta::Enable,
+            (_, true) => Delta::Disable,
+            (false, false) => Delta::Keep,
+        }
+    }
+
+    /// Writes the tri-state mark for a single feature, clearing whatever was
+    /// there before.
+    pub fn set_delta(&mut self, feature: Feature, delta: Delta) {
+        self.remove(feature.on_bit() | feature.off_bit());
+        match delta {
+            Delta::Enable => self.insert(feature.on_bit()),
+            Delta::Disable => self.insert(feature.off_bit()),
+            Delta::Keep => {}
+        }
+    }
+}
+
+impl fmt::Display for PaxFlags {
+    /// Renders back to the canonical `PEMRS`-ordered mark string, omitting
+    /// any feature left at the kernel default.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for feature in Feature::ALL {
+            match self.get(feature) {
+                Delta::Enable => write!(f, "{}", feature.letter())?,
+                Delta::Disable => write!(f, "{}", feature.letter().to_ascii_lowercase())?,
+                Delta::Keep => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A requested change to a single PaX feature: force it on, force it off, or
+/// leave whatever was already there.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Delta {
+    Enable,
+    Disable,
+    Keep,
+}
+
+impl Delta {
+    pub fn new(enable: bool, disable: bool) -> Self {
+        use Delta::*;
+        match (enable, disable) {
+            (true, _) => Enable,
+            (_, true) => Disable,
+            _ => Keep,
+        }
+    }
+
+    /// Applies this delta over an existing mark, preferring this delta
+    /// unless it requests no change, in which case the existing mark wins.
+    pub fn apply(self, current: Delta) -> Delta {
+        match self {
+            Delta::Keep => current,
+            explicit => explicit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Delta::*;
+
+    #[test]
+    fn cli_to_flag_delta() {
+        // new(enabled, disabled) -> Delta
+        // Precedence:
+        // - if enabled  -> Enable
+        // - if disabled -> Disable
+        // - if neither  -> Keep
+        assert_eq!(Delta::new(true, true), Enable);
+        assert_eq!(Delta::new(true, false), Enable);
+        assert_eq!(Delta::new(false, true), Disable);
+        assert_eq!(Delta::new(false, false), Keep);
+    }
+
+    #[test]
+    fn delta_apply() {
+        assert_eq!(Enable.apply(Disable), Enable);
+        assert_eq!(Disable.apply(Enable), Disable);
+        assert_eq!(Keep.apply(Enable), Enable);
+        assert_eq!(Keep.apply(Disable), Disable);
+        assert_eq!(Keep.apply(Keep), Keep);
+    }
+
+    #[test]
+    fn parse_roundtrip() {
+        let flags = PaxFlags::parse("PeMrS").unwrap();
+        assert_eq!(flags.get(Feature::PageExec), Enable);
+        assert_eq!(flags.get(Feature::Emutramp), Disable);
+        assert_eq!(flags.get(Feature::Mprotect), Enable);
+        assert_eq!(flags.get(Feature::Randmmap), Disable);
+        assert_eq!(flags.get(Feature::Segmexec), Enable);
+        assert_eq!(flags.to_string(), "PeMrS");
+    }
+
+    #[test]
+    fn parse_rejects_unknown_mark() {
+        assert_eq!(PaxFlags::parse("PQ"), Err(ParseFlagsError::UnknownMark('Q')));
+    }
+
+    #[test]
+    fn parse_rejects_duplicate_mark() {
+        assert_eq!(PaxFlags::parse("PEp"), Err(ParseFlagsError::DuplicateMark('P')));
+    }
+
+    #[test]
+    fn default_is_omitted_from_display() {
+        let mut flags = PaxFlags::empty();
+        flags.set_delta(Feature::Mprotect, Enable);
+        assert_eq!(flags.to_string(), "M");
+    }
+}
@@ -1,36 +1,80 @@
+mod elf;
+mod flags;
+mod output;
+
 use std::{
     collections::BTreeMap,
+    fmt,
     path::{Path, PathBuf},
 };
 
-use clap::Parser;
-use xattr;
-
-const USER_PAX_FLAGS: &str = "user.pax.flags";
-const HELP_MSG: &str = "
-paxmark - a utility for setting PaX markings on binaries
+use clap::{Parser, Subcommand};
 
-Usage:
-% paxmark -[pP|eE|mM|rR|sS] <binary>
+use flags::{Delta, Feature, PaxFlags};
+use output::{OutputFormat, Report};
 
-Each letter corresponds to a PaX feature flag, upper case
-enabling and lower case disabling.
+const USER_PAX_FLAGS: &str = "user.pax.flags";
 
-This utility will clear out invalid marks and explicitly set
-enabled for missing marks, matching defaults.
+/// Which on-disk representation(s) of the PaX marks to operate on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, clap::ValueEnum)]
+enum Format {
+    /// The `user.pax.flags` extended attribute only.
+    Xattr,
+    /// The ELF `PT_PAX_FLAGS` program header only.
+    Elf,
+    /// Both the xattr and the ELF program header.
+    Both,
+}
 
--p|P    PAGEEXEC: use NX-bit to mark unexecutable pages
--e|E    EMUTRAMP: emulate stack trampolines
--m|M    MPROTECT: write-xor-execute in mmap/mprotect(2) syscalls
--r|R    RANDMMAP: address space layout randomization (ASLR)
--s|S    SEGMEXEC: segmentation-based NX-bit emulation
-";
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Format::Xattr => "xattr",
+            Format::Elf => "elf",
+            Format::Both => "both",
+        })
+    }
+}
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
-    #[arg(value_hint = clap::ValueHint::DirPath)]
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the current marks on a binary in canonical form
+    Get(GetArgs),
+    /// Set PaX marks on a binary
+    Set(SetArgs),
+    /// Clear a binary's marks back to kernel defaults
+    Reset(ResetArgs),
+    /// Compare the marks on two binaries
+    Diff(DiffArgs),
+}
+
+#[derive(Parser)]
+struct GetArgs {
+    #[arg(value_hint = clap::ValueHint::FilePath)]
     binary: PathBuf,
+    #[arg(long, value_enum, default_value_t = Format::Both)]
+    format: Format,
+    #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+    output: OutputFormat,
+}
+
+#[derive(Parser)]
+struct SetArgs {
+    #[arg(required = true, num_args = 1.., value_hint = clap::ValueHint::AnyPath)]
+    binaries: Vec<PathBuf>,
+    /// Walk directory arguments and apply the same marks to every ELF found inside
+    #[arg(long)]
+    recursive: bool,
+    /// Compute and print the new marks for each target without writing them
+    #[arg(long)]
+    dry_run: bool,
     #[arg(short = 'P', group = "pageexec")]
     e_pageexec: bool,
     #[arg(short = 'p', group = "pageexec")]
@@ -51,112 +95,393 @@ struct Cli {
     e_segmexec: bool,
     #[arg(short = 's', group = "segmexec")]
     d_segmexec: bool,
+    #[arg(long, value_enum, default_value_t = Format::Both)]
+    format: Format,
+    #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+    output: OutputFormat,
 }
 
-impl Cli {
+impl SetArgs {
     // We don't need to test this because we test Delta::new
-    fn get_delta(&self) -> BTreeMap<char, Delta> {
+    fn get_delta(&self) -> BTreeMap<Feature, Delta> {
         BTreeMap::from([
-            ('P', Delta::new(self.e_pageexec, self.d_pageexec)),
-            ('E', Delta::new(self.e_emutramp, self.d_emutramp)),
-            ('M', Delta::new(self.e_mprotect, self.d_mprotect)),
-            ('R', Delta::new(self.e_randmmap, self.d_randmmap)),
-            ('S', Delta::new(self.e_segmexec, self.d_segmexec)),
+            (Feature::PageExec, Delta::new(self.e_pageexec, self.d_pageexec)),
+            (Feature::Emutramp, Delta::new(self.e_emutramp, self.d_emutramp)),
+            (Feature::Mprotect, Delta::new(self.e_mprotect, self.d_mprotect)),
+            (Feature::Randmmap, Delta::new(self.e_randmmap, self.d_randmmap)),
+            (Feature::Segmexec, Delta::new(self.e_segmexec, self.d_segmexec)),
         ])
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-enum Delta {
-    Enable,
-    Disable,
-    Keep,
+#[derive(Parser)]
+struct ResetArgs {
+    #[arg(value_hint = clap::ValueHint::FilePath)]
+    binary: PathBuf,
+    #[arg(long, value_enum, default_value_t = Format::Both)]
+    format: Format,
+}
+
+#[derive(Parser)]
+struct DiffArgs {
+    #[arg(value_hint = clap::ValueHint::FilePath)]
+    a: PathBuf,
+    #[arg(value_hint = clap::ValueHint::FilePath)]
+    b: PathBuf,
+    #[arg(long, value_enum, default_value_t = Format::Both)]
+    format: Format,
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Command::Get(args) => cmd_get(args),
+        Command::Set(args) => cmd_set(args),
+        Command::Reset(args) => cmd_reset(args),
+        Command::Diff(args) => cmd_diff(args),
+    }
+}
+
+/// `paxmark get <binary>`: a safe, read-only print of the current marks.
+fn cmd_get(args: GetArgs) {
+    let current = read_marks(&args.binary, args.format);
+    Report::new(current.flags, current.raw, current.dirty).print(args.output);
 }
 
-impl Delta {
-    fn new(enable: bool, disable: bool) -> Self {
-        use Delta::*;
-        match (enable, disable) {
-            (true, _) => Enable,
-            (_, true) => Disable,
-            _ => Keep,
-        }
+/// Tally of what happened across every target of a batch `set`.
+#[derive(Debug, Default)]
+struct Summary {
+    changed: usize,
+    unchanged: usize,
+    skipped: usize,
+    errored: usize,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} changed, {} unchanged, {} skipped, {} errored",
+            self.changed, self.unchanged, self.skipped, self.errored
+        )
     }
+}
+
+/// `paxmark set -[pP|eE|mM|rR|sS] <binary>...`: today's mutate-on-run
+/// behaviour, extended to run over every target path (walking directories
+/// when `--recursive` is given) and to support `--dry-run`.
+fn cmd_set(args: SetArgs) {
+    let delta = args.get_delta();
+    let human = args.output == OutputFormat::Human;
+
+    let (targets, skipped_non_elf, skipped_explicit) = collect_targets(&args.binaries, args.recursive);
+    let mut summary = Summary {
+        skipped: skipped_non_elf,
+        // A path named directly on the command line that turns out not to be
+        // an ELF file is a failure to do what was asked, not background
+        // noise from a directory walk, so it counts toward the exit code.
+        errored: skipped_explicit,
+        ..Summary::default()
+    };
+
+    for target in &targets {
+        let current = read_marks(target, args.format);
+
+        // For each feature, the CLI's requested delta wins; if the CLI
+        // didn't ask for a change, fall back to whatever was already
+        // marked. Anything still unresolved after that matches kernel
+        // defaults, which are enabled, so we write that out explicitly.
+        let mut new = PaxFlags::empty();
+        for feature in Feature::ALL {
+            let requested = delta.get(&feature).copied().unwrap_or(Delta::Keep);
+            let mut resolved = requested.apply(current.flags.get(feature));
+            if resolved == Delta::Keep {
+                resolved = Delta::Enable;
+            }
+            new.set_delta(feature, resolved);
+        }
 
-    fn apply(self, c: char) -> char {
-        use Delta::*;
-        match self {
-            Enable => c.to_ascii_uppercase(),
-            Disable => c.to_ascii_lowercase(),
-            Keep => c,
+        if current.already_matches(new) {
+            summary.unchanged += 1;
+            if human {
+                println!("{}: unchanged ({new})", target.display());
+            }
+            continue;
+        }
+
+        if args.dry_run {
+            summary.changed += 1;
+            if human {
+                println!("{}: would set to {new} (dry run)", target.display());
+            } else {
+                Report::new(new, new.to_string(), current.dirty).print(args.output);
+            }
+            continue;
         }
+
+        if write_marks(target, args.format, new, false) {
+            summary.errored += 1;
+            continue;
+        }
+        if human {
+            println!("{}: set to {new}", target.display());
+        } else {
+            Report::new(new, new.to_string(), current.dirty).print(args.output);
+        }
+        summary.changed += 1;
+    }
+
+    // The summary is prose, not part of the structured output, so it stays
+    // off stdout when a script is expecting to parse each target's Report.
+    if human {
+        println!("{summary}");
+    } else {
+        eprintln!("{summary}");
+    }
+    if summary.errored > 0 {
+        std::process::exit(1);
     }
 }
 
-fn main() {
-    // This is easier than properly adding the help message to the derive-based Parser
-    use clap::error::ErrorKind as ClapErrKind;
-    let cli = match Cli::try_parse() {
-        // Happy case with usable args
-        Ok(x) => x,
-        // Unhappy case that we're fine letting clap handle
-        Err(err) if err.kind() != ClapErrKind::DisplayHelp => {
-            err.exit();
-        }
-        _ => {
-            // Print the help message by hand
-            println!("{HELP_MSG}");
-            std::process::exit(0);
+/// Resolves the CLI's path arguments into concrete ELF files to mark,
+/// walking directories when `recursive` is set. Returns the targets found,
+/// a count of non-ELF files turned up while walking a directory, and a
+/// count of non-ELF files named explicitly on the command line.
+///
+/// The two counts are kept apart because they mean different things: a
+/// directory walk turning up a non-ELF file along the way is the expected,
+/// noisy case and is fine to just tally, but a path the user named directly
+/// was pointed at on purpose, so it's reported per-path and treated as a
+/// failure to do what was asked.
+fn collect_targets(paths: &[PathBuf], recursive: bool) -> (Vec<PathBuf>, usize, usize) {
+    let mut targets = Vec::new();
+    let mut skipped_non_elf = 0;
+    let mut skipped_explicit = 0;
+    let mut pending: Vec<(PathBuf, bool)> = paths.iter().cloned().map(|path| (path, true)).collect();
+
+    while let Some((path, explicit)) = pending.pop() {
+        if path.is_dir() {
+            if !recursive {
+                eprintln!("{}: is a directory, skipping (pass --recursive to walk it)", path.display());
+                continue;
+            }
+            match std::fs::read_dir(&path) {
+                Ok(entries) => {
+                    pending.extend(entries.filter_map(|e| e.ok()).map(|e| (e.path(), false)));
+                }
+                Err(err) => eprintln!("{}: error reading directory: {err}", path.display()),
+            }
+        } else if is_elf(&path) {
+            targets.push(path);
+        } else if explicit {
+            eprintln!("{}: not an ELF file, skipping", path.display());
+            skipped_explicit += 1;
+        } else {
+            skipped_non_elf += 1;
         }
+    }
+
+    targets.sort();
+    (targets, skipped_non_elf, skipped_explicit)
+}
+
+/// Sniffs the ELF magic number without parsing the whole file.
+fn is_elf(path: &Path) -> bool {
+    use std::io::Read;
+    let Ok(mut file) = std::fs::File::open(path) else {
+        return false;
     };
+    let mut magic = [0u8; 4];
+    file.read_exact(&mut magic).is_ok() && magic == *b"\x7fELF"
+}
 
-    // Get the current xattr value, print it for transparency
-    let current = get_value(&cli.binary);
-    println!("Current {USER_PAX_FLAGS} xattr value: {current}");
-
-    let mut valid_current = true;
-
-    // Map of marks to if/how to change them, derived from the CLI flags
-    let mut delta = cli.get_delta();
-
-    // Iterate over the current state, removing the deltas so we can only have one
-    // match per mark.
-    //
-    // For each match, apply the delta. Each non match is either a duplicate or invalid
-    // mark, so the current state is dirty. We'll use the first matches in the value as
-    // valid, filtering out the rest.
-    //
-    // TODO: extract and test against proper and improper current values
-    let mut new = current
-        .chars()
-        .filter_map(|c| match delta.remove(&c.to_ascii_uppercase()) {
-            Some(d) => Some(d.apply(c)),
-            None => {
-                valid_current = false;
-                None
+/// `paxmark reset <binary>`: clears marks back to kernel defaults.
+fn cmd_reset(args: ResetArgs) {
+    if matches!(args.format, Format::Xattr | Format::Both) {
+        match remove_value(&args.binary) {
+            Ok(()) => println!("Cleared {USER_PAX_FLAGS} xattr"),
+            Err(err) => eprintln!("Error clearing xattr value: {err}"),
+        }
+    }
+
+    if matches!(args.format, Format::Elf | Format::Both) {
+        match elf::set_value(&args.binary, PaxFlags::empty()) {
+            Ok(()) => println!("Cleared PT_PAX_FLAGS"),
+            // Most binaries simply have no PT_PAX_FLAGS segment to begin
+            // with; under --format both that's not a failure as long as the
+            // xattr clear above already took care of this binary.
+            Err(err) if args.format == Format::Both && err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => eprintln!("Error clearing PT_PAX_FLAGS: {err}"),
+        }
+    }
+}
+
+/// `paxmark diff <a> <b>`: a safe, read-only comparison of two binaries.
+///
+/// Compares each backend that was actually read independently, rather than
+/// collapsing to a single merged value, so a divergence in just the ELF
+/// header (or just the xattr) under `--format both` isn't missed.
+fn cmd_diff(args: DiffArgs) {
+    let a = read_marks(&args.a, args.format);
+    let b = read_marks(&args.b, args.format);
+
+    let mut any_diff = false;
+    for (label, a_flags) in a.backends() {
+        let Some(b_flags) = b.backend(label) else { continue };
+        for feature in Feature::ALL {
+            let (fa, fb) = (a_flags.get(feature), b_flags.get(feature));
+            if fa != fb {
+                any_diff = true;
+                println!("{label} {}: {fa:?} -> {fb:?}", feature.letter());
             }
-        })
-        .collect::<String>();
+        }
+    }
 
-    // The remaining keys are marks that weren't matched in the current xattr value, ergo
-    // are missing and should have their defaults added. The keys are capitalised, which
-    // means enabled, so we can simply add the keys as the marks.
-    for (key, _) in delta.into_iter() {
-        new.push(key);
+    if !any_diff {
+        println!("No difference");
     }
+}
 
-    // Just let the user know, so there are no surprises.
-    if !valid_current {
-        eprintln!("The old {USER_PAX_FLAGS} value is either dirty or invalid");
-        eprintln!("Only the first valid marks from this value will be used");
+/// The marks read back for a binary, alongside enough detail to render a
+/// [`Report`] and to compare individual backends against each other.
+struct MarksRead {
+    /// The value used to resolve a `set` delta against: the xattr when it
+    /// was read, otherwise the ELF header.
+    flags: PaxFlags,
+    raw: String,
+    dirty: bool,
+    /// The xattr's marks, if the xattr backend was selected.
+    xattr: Option<PaxFlags>,
+    /// The ELF header's marks, if the ELF backend was selected and the
+    /// binary actually has a `PT_PAX_FLAGS` segment.
+    elf: Option<PaxFlags>,
+}
+
+impl MarksRead {
+    /// Every backend that was actually read, labelled for diagnostics.
+    fn backends(&self) -> Vec<(&'static str, PaxFlags)> {
+        let mut backends = Vec::new();
+        if let Some(flags) = self.xattr {
+            backends.push(("xattr", flags));
+        }
+        if let Some(flags) = self.elf {
+            backends.push(("elf", flags));
+        }
+        backends
     }
 
-    // And finally apply the new value
-    if let Err(err) = set_value(&cli.binary, &new) {
-        eprintln!("Error on setting xattr value: {err}");
+    /// Looks up a single backend's marks by label (`"xattr"` or `"elf"`).
+    fn backend(&self, label: &str) -> Option<PaxFlags> {
+        match label {
+            "xattr" => self.xattr,
+            "elf" => self.elf,
+            _ => None,
+        }
+    }
+
+    /// Whether every backend that was actually read already holds `new`. A
+    /// backend that wasn't read (not selected, or no `PT_PAX_FLAGS` segment
+    /// to begin with) can't be stale, so it doesn't block this. When no
+    /// backend was read at all, nothing matches.
+    fn already_matches(&self, new: PaxFlags) -> bool {
+        let backends = self.backends();
+        !backends.is_empty() && backends.iter().all(|(_, flags)| *flags == new)
+    }
+}
+
+/// Reads the current marks for `binary` from whichever backend(s) were
+/// selected, printing diagnostics about dirty or missing state as it goes.
+/// The xattr is authoritative for resolving a `set` delta when both are in
+/// play, since it was the original source of truth.
+fn read_marks(binary: &Path, format: Format) -> MarksRead {
+    let xattr = if matches!(format, Format::Xattr | Format::Both) {
+        let raw = get_value(binary);
+        eprintln!("Current {USER_PAX_FLAGS} xattr value: {raw}");
+
+        // A parse failure means the value is dirty or invalid (unknown or
+        // duplicate marks), in which case we fall back to assuming kernel
+        // defaults rather than guessing which marks were meant.
+        match PaxFlags::parse(&raw) {
+            Ok(flags) => Some((flags, raw, false)),
+            Err(err) => {
+                eprintln!("The old {USER_PAX_FLAGS} value is either dirty or invalid: {err}");
+                eprintln!("Only kernel defaults will be assumed for this binary");
+                Some((PaxFlags::empty(), raw, true))
+            }
+        }
+    } else {
+        None
+    };
+
+    let elf = if matches!(format, Format::Elf | Format::Both) {
+        match elf::get_value(binary) {
+            Ok(Some(flags)) => {
+                eprintln!("Current PT_PAX_FLAGS value: {flags}");
+                let bits = elf::raw_p_flags(binary).ok().flatten().unwrap_or_default();
+                Some((flags, format!("{bits:#010x}"), false))
+            }
+            Ok(None) => {
+                eprintln!("No PT_PAX_FLAGS segment present, assuming kernel defaults");
+                None
+            }
+            Err(err) => {
+                eprintln!("Error reading PT_PAX_FLAGS: {err}");
+                None
+            }
+        }
     } else {
-        println!("Set {USER_PAX_FLAGS} xattr to {new} successfully!");
+        None
+    };
+
+    let xattr_flags = xattr.as_ref().map(|(flags, ..)| *flags);
+    let elf_flags = elf.as_ref().map(|(flags, ..)| *flags);
+
+    // The xattr is the primary source used to resolve a `set` delta; fall
+    // back to the ELF header only when the xattr backend wasn't read at all.
+    let (flags, raw, dirty) = xattr.or(elf).unwrap_or((PaxFlags::empty(), String::new(), false));
+
+    MarksRead { flags, raw, dirty, xattr: xattr_flags, elf: elf_flags }
+}
+
+/// Writes `new` to whichever backend(s) were selected, printing a
+/// confirmation for each when `human` is set. Returns whether any backend
+/// failed to write.
+fn write_marks(binary: &Path, format: Format, new: PaxFlags, human: bool) -> bool {
+    let mut failed = false;
+
+    if matches!(format, Format::Xattr | Format::Both) {
+        match set_value(binary, new.to_string()) {
+            Ok(()) if human => println!("Set {USER_PAX_FLAGS} xattr to {new} successfully!"),
+            Ok(()) => {}
+            Err(err) => {
+                eprintln!("{}: error setting xattr value: {err}", binary.display());
+                failed = true;
+            }
+        }
     }
+
+    if matches!(format, Format::Elf | Format::Both) {
+        match elf::set_value(binary, new) {
+            Ok(()) if human => println!("Set PT_PAX_FLAGS to {new} successfully!"),
+            Ok(()) => {}
+            // Most binaries simply have no PT_PAX_FLAGS segment to begin
+            // with. That's only a hard failure when the ELF header was the
+            // only backend asked for; with `--format both` the xattr write
+            // above already covers this binary.
+            Err(err) if format == Format::Both && err.kind() == std::io::ErrorKind::NotFound => {
+                if human {
+                    println!("{}: no PT_PAX_FLAGS segment, xattr only", binary.display());
+                }
+            }
+            Err(err) => {
+                eprintln!("{}: error setting PT_PAX_FLAGS: {err}", binary.display());
+                failed = true;
+            }
+        }
+    }
+
+    failed
 }
 
 fn get_value(binary: impl AsRef<Path>) -> String {
@@ -172,35 +497,97 @@ fn set_value(binary: impl AsRef<Path>, value: impl AsRef<[u8]>) -> std::io::Resu
     xattr::set(binary, USER_PAX_FLAGS, value.as_ref())
 }
 
+fn remove_value(binary: impl AsRef<Path>) -> std::io::Result<()> {
+    match xattr::remove(binary, USER_PAX_FLAGS) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
     use super::*;
-    use Delta::*;
+
+    /// A scratch directory under the system temp dir, removed on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> TempDir {
+            static COUNTER: AtomicU32 = AtomicU32::new(0);
+            let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+            let path = std::env::temp_dir().join(format!("paxmark-test-{}-{id}", std::process::id()));
+            std::fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+
+        fn path(&self) -> &Path {
+            &self.0
+        }
+
+        fn write_elf(&self, name: &str) -> PathBuf {
+            let path = self.0.join(name);
+            std::fs::write(&path, b"\x7fELFgarbage").unwrap();
+            path
+        }
+
+        fn write_non_elf(&self, name: &str) -> PathBuf {
+            let path = self.0.join(name);
+            std::fs::write(&path, b"not an elf file").unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
 
     #[test]
-    fn cli_to_flag_delta() {
-        // new(enabled, disabled) -> Delta
-        // Precedence:
-        // - if enabled  -> Enable
-        // - if disabled -> Disable
-        // - if neither  -> Keep
-        assert_eq!(Delta::new(true, true), Enable);
-        assert_eq!(Delta::new(true, false), Enable);
-        assert_eq!(Delta::new(false, true), Disable);
-        assert_eq!(Delta::new(false, false), Keep);
+    fn collect_targets_finds_explicit_elf_files() {
+        let dir = TempDir::new();
+        let elf = dir.write_elf("a.elf");
+
+        let (targets, skipped_non_elf, skipped_explicit) = collect_targets(&[elf.clone()], false);
+        assert_eq!(targets, vec![elf]);
+        assert_eq!(skipped_non_elf, 0);
+        assert_eq!(skipped_explicit, 0);
     }
 
     #[test]
-    fn delta_apply() {
-        let e = Enable;
-        let d = Disable;
-        let k = Keep;
-
-        assert_eq!(e.apply('Q'), 'Q');
-        assert_eq!(e.apply('q'), 'Q');
-        assert_eq!(d.apply('Q'), 'q');
-        assert_eq!(d.apply('q'), 'q');
-        assert_eq!(k.apply('Q'), 'Q');
-        assert_eq!(k.apply('q'), 'q');
+    fn collect_targets_reports_explicit_non_elf_paths() {
+        let dir = TempDir::new();
+        let script = dir.write_non_elf("script.sh");
+
+        let (targets, skipped_non_elf, skipped_explicit) = collect_targets(&[script], false);
+        assert!(targets.is_empty());
+        assert_eq!(skipped_non_elf, 0);
+        assert_eq!(skipped_explicit, 1);
+    }
+
+    #[test]
+    fn collect_targets_silently_counts_non_elf_found_while_recursing() {
+        let dir = TempDir::new();
+        dir.write_elf("a.elf");
+        dir.write_non_elf("readme.txt");
+
+        let (targets, skipped_non_elf, skipped_explicit) = collect_targets(&[dir.path().to_path_buf()], true);
+        assert_eq!(targets.len(), 1);
+        assert_eq!(skipped_non_elf, 1);
+        assert_eq!(skipped_explicit, 0);
+    }
+
+    #[test]
+    fn collect_targets_skips_directories_unless_recursive() {
+        let dir = TempDir::new();
+        dir.write_elf("a.elf");
+
+        let (targets, skipped_non_elf, skipped_explicit) = collect_targets(&[dir.path().to_path_buf()], false);
+        assert!(targets.is_empty());
+        assert_eq!(skipped_non_elf, 0);
+        assert_eq!(skipped_explicit, 0);
     }
 }
@@ -0,0 +1,101 @@
+//! Machine-consumable rendering of a binary's PaX marks, for `get` and the
+//! result of `set`.
+
+use std::{collections::BTreeMap, fmt};
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::flags::{Delta, Feature, PaxFlags};
+
+/// How to print the marks read (or written) for a binary.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Prose, for a human at a terminal.
+    #[default]
+    Human,
+    /// A structured JSON object, for scripts and config-management tooling.
+    #[cfg(feature = "serde")]
+    Json,
+    /// The bare canonical mark string (e.g. `PeMrS`), for piping.
+    Flags,
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OutputFormat::Human => "human",
+            #[cfg(feature = "serde")]
+            OutputFormat::Json => "json",
+            OutputFormat::Flags => "flags",
+        })
+    }
+}
+
+/// The state of a single PaX feature, as reported to machine consumers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum FeatureState {
+    Enabled,
+    Disabled,
+    Default,
+}
+
+impl From<Delta> for FeatureState {
+    fn from(delta: Delta) -> Self {
+        match delta {
+            Delta::Enable => FeatureState::Enabled,
+            Delta::Disable => FeatureState::Disabled,
+            Delta::Keep => FeatureState::Default,
+        }
+    }
+}
+
+/// A full report of a binary's marks, covering every [`OutputFormat`].
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Report {
+    /// The canonical `PaxFlags` this report describes.
+    pub flags: PaxFlags,
+    /// Per-feature state, keyed by the feature's canonical letter.
+    pub features: BTreeMap<char, FeatureState>,
+    /// The stored value behind the marks: for a read, the xattr string as
+    /// read or the ELF `p_flags` rendered as hex; for the result of a `set`,
+    /// the new canonical mark string.
+    pub raw: String,
+    /// Whether the prior stored value was dirty or invalid and had to be
+    /// discarded in favour of kernel defaults.
+    pub dirty: bool,
+}
+
+impl Report {
+    pub fn new(flags: PaxFlags, raw: String, dirty: bool) -> Report {
+        let features = Feature::ALL
+            .into_iter()
+            .map(|feature| (feature.letter(), FeatureState::from(flags.get(feature))))
+            .collect();
+        Report { flags, features, raw, dirty }
+    }
+
+    /// Prints this report in the requested format.
+    pub fn print(&self, format: OutputFormat) {
+        match format {
+            OutputFormat::Human => {
+                println!("Marks: {}", self.flags);
+                for feature in Feature::ALL {
+                    println!("  {}: {:?}", feature.letter(), self.features[&feature.letter()]);
+                }
+                if self.dirty {
+                    println!("(previous value was dirty or invalid; kernel defaults were assumed)");
+                }
+            }
+            #[cfg(feature = "serde")]
+            OutputFormat::Json => match serde_json::to_string_pretty(self) {
+                Ok(json) => println!("{json}"),
+                Err(err) => eprintln!("Error serializing report: {err}"),
+            },
+            OutputFormat::Flags => println!("{}", self.flags),
+        }
+    }
+}